@@ -0,0 +1,118 @@
+// config.rs
+
+// 读取一份 JSON 作业描述，针对同一设备依序执行其中的 upload/test/erase/reset 步骤，
+// 遇到第一个错误即停止，从而用一次调用完成整条烧写流水线。
+
+use crate::image::{erase, test, upload, verify_image_file};
+use crate::default::reset;
+use crate::transfer::SerialSpecs;
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// JSON 中的 `SerialSpecs` 块，所有字段均可选，缺省时回退到 `Default`
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SpecsConfig {
+    pub device: Option<String>,
+    pub initial_timeout_s: Option<u32>,
+    pub subsequent_timeout_ms: Option<u32>,
+    pub nb_retry: Option<u32>,
+    pub linelength: Option<usize>,
+    pub mtu: Option<usize>,
+    pub baudrate: Option<u32>,
+}
+
+impl SpecsConfig {
+    /// 将可选字段折叠进 `SerialSpecs::default()`
+    fn to_specs(&self) -> SerialSpecs {
+        let d = SerialSpecs::default();
+        SerialSpecs {
+            device: self.device.clone().unwrap_or(d.device),
+            initial_timeout_s: self.initial_timeout_s.unwrap_or(d.initial_timeout_s),
+            subsequent_timeout_ms: self.subsequent_timeout_ms.unwrap_or(d.subsequent_timeout_ms),
+            nb_retry: self.nb_retry.unwrap_or(d.nb_retry),
+            linelength: self.linelength.unwrap_or(d.linelength),
+            mtu: self.mtu.unwrap_or(d.mtu),
+            baudrate: self.baudrate.unwrap_or(d.baudrate),
+        }
+    }
+}
+
+/// 序列中的单个步骤
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Upload {
+        file: PathBuf,
+        slot: u8,
+        /// 可选：烧写前解析镜像并比对内嵌 SHA-256，默认关闭
+        #[serde(default)]
+        verify: bool,
+    },
+    Test { hash: String, confirm: bool },
+    Erase { slot: u8 },
+    Reset {},
+}
+
+/// 一个完整的作业描述：一份 `SerialSpecs` 加上有序的步骤列表
+#[derive(Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub specs: SpecsConfig,
+    pub steps: Vec<Step>,
+}
+
+/// 执行失败的原因：读取/解析作业描述失败，或某个步骤执行失败
+pub enum ConfigError {
+    /// 读取或解析 JSON 作业描述失败（尚未执行任何步骤）
+    Read(anyhow::Error),
+    /// 第 `index` 个步骤执行失败
+    Step { index: usize, error: anyhow::Error },
+}
+
+/// 读取 JSON 作业描述并依序执行，遇到第一个错误即停止；
+/// 全部成功时返回已执行的步骤数
+pub fn run_config(path: &Path) -> std::result::Result<usize, ConfigError> {
+    let cfg = read_config(path).map_err(ConfigError::Read)?;
+    let specs = cfg.specs.to_specs();
+
+    for (index, step) in cfg.steps.iter().enumerate() {
+        run_step(&specs, step).map_err(|error| ConfigError::Step { index, error })?;
+    }
+    Ok(cfg.steps.len())
+}
+
+fn read_config(path: &Path) -> Result<Config> {
+    let data = fs::read_to_string(path)?;
+    let cfg = serde_json::from_str(&data)?;
+    Ok(cfg)
+}
+
+fn run_step(specs: &SerialSpecs, step: &Step) -> Result<()> {
+    match step {
+        Step::Upload { file, slot, verify } => {
+            // 仅在显式要求时预检镜像：魔数、TLV 边界与内嵌 SHA-256
+            if *verify {
+                verify_image_file(file)?;
+            }
+            let cancel = Arc::new(AtomicBool::new(false));
+            upload(specs, file, *slot, cancel, None::<fn(u64, u64)>)?;
+        }
+        Step::Test { hash, confirm } => {
+            let hash_bytes = hex::decode(hash)?;
+            test(specs, hash_bytes, Some(*confirm))?;
+        }
+        Step::Erase { slot } => {
+            erase(specs, Some(*slot as u32))?;
+        }
+        Step::Reset {} => {
+            reset(specs)?;
+        }
+    }
+    Ok(())
+}
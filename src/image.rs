@@ -0,0 +1,255 @@
+// image.rs
+
+// MCUboot 镜像解析：在 upload 真正烧写之前，先解析镜像头与 TLV 区，
+// 以便调用方校验二进制是否有效，并读取其版本/哈希而无需烧写。
+
+use anyhow::{bail, Result};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// MCUboot 镜像头魔数
+const IMAGE_MAGIC: u32 = 0x96f3_b83d;
+/// 未加保护的 TLV 信息区魔数
+const IMAGE_TLV_INFO_MAGIC: u16 = 0x6907;
+/// 受保护的 TLV 信息区魔数
+const IMAGE_TLV_PROT_INFO_MAGIC: u16 = 0x6908;
+/// SHA-256 哈希的 TLV 类型
+const IMAGE_TLV_SHA256: u8 = 0x10;
+
+/// 镜像版本 `major.minor.revision+build`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub revision: u16,
+    pub build_num: u32,
+}
+
+impl std::fmt::Display for ImageVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}+{}",
+            self.major, self.minor, self.revision, self.build_num
+        )
+    }
+}
+
+/// 解析出的镜像信息
+#[derive(Debug, Clone)]
+pub struct ImageInfo {
+    pub version: ImageVersion,
+    pub img_size: u32,
+    pub flags: u32,
+    pub sha256: Vec<u8>,
+}
+
+/// 按小端读取一个 `u16`
+fn read_u16(data: &[u8], off: usize) -> Result<u16> {
+    let end = off + 2;
+    if end > data.len() {
+        bail!("image truncated: need {} bytes, have {}", end, data.len());
+    }
+    Ok(u16::from_le_bytes([data[off], data[off + 1]]))
+}
+
+/// 按小端读取一个 `u32`
+fn read_u32(data: &[u8], off: usize) -> Result<u32> {
+    let end = off + 4;
+    if end > data.len() {
+        bail!("image truncated: need {} bytes, have {}", end, data.len());
+    }
+    Ok(u32::from_le_bytes([
+        data[off],
+        data[off + 1],
+        data[off + 2],
+        data[off + 3],
+    ]))
+}
+
+/// 解析 MCUboot 镜像，校验魔数、TLV 边界与 SHA-256，返回版本/大小/标志/哈希
+pub fn parse_image(data: &[u8]) -> Result<ImageInfo> {
+    // 32 字节小端镜像头
+    let magic = read_u32(data, 0)?;
+    if magic != IMAGE_MAGIC {
+        bail!("bad image magic: {:#010x} != {:#010x}", magic, IMAGE_MAGIC);
+    }
+    // load_addr 偏移 4，这里不需要
+    let hdr_size = read_u16(data, 8)? as usize;
+    // protect_tlv_size 偏移 10：受保护 TLV 区的字节数，会被计入 SHA-256 摘要
+    let protect_tlv_size = read_u16(data, 10)? as usize;
+    let img_size = read_u32(data, 12)?;
+    let flags = read_u32(data, 16)?;
+    let version = ImageVersion {
+        major: data.get(20).copied().unwrap_or_default(),
+        minor: data.get(21).copied().unwrap_or_default(),
+        revision: read_u16(data, 22)?,
+        build_num: read_u32(data, 24)?,
+    };
+
+    // 跳过 hdr_size 到达镜像数据；在 hdr_size + img_size 处是（可选的）受保护 TLV 区，
+    // 其后才是承载 SHA-256 的未保护 TLV 区
+    let img_end = hdr_size
+        .checked_add(img_size as usize)
+        .ok_or_else(|| anyhow::anyhow!("image size overflow"))?;
+
+    // 受保护 TLV 区（若存在）整块计入 SHA-256 摘要；校验其信息头并跳过它
+    if protect_tlv_size > 0 {
+        let prot_magic = read_u16(data, img_end)?;
+        if prot_magic != IMAGE_TLV_PROT_INFO_MAGIC {
+            bail!("bad protected TLV info magic: {:#06x}", prot_magic);
+        }
+    }
+    let tlv_off = img_end
+        .checked_add(protect_tlv_size)
+        .ok_or_else(|| anyhow::anyhow!("protected TLV size overflow"))?;
+
+    let info_magic = read_u16(data, tlv_off)?;
+    if info_magic != IMAGE_TLV_INFO_MAGIC && info_magic != IMAGE_TLV_PROT_INFO_MAGIC {
+        bail!("bad TLV info magic: {:#06x}", info_magic);
+    }
+    let tlv_tot = read_u16(data, tlv_off + 2)? as usize;
+    let tlv_end = tlv_off
+        .checked_add(tlv_tot)
+        .ok_or_else(|| anyhow::anyhow!("TLV area overflow"))?;
+    if tlv_end > data.len() {
+        bail!("TLV area runs past buffer: {} > {}", tlv_end, data.len());
+    }
+
+    // 遍历 TLV 条目，找到 SHA-256
+    let mut sha256: Option<Vec<u8>> = None;
+    let mut off = tlv_off + 4;
+    while off + 4 <= tlv_end {
+        let tlv_type = data[off];
+        let len = read_u16(data, off + 2)? as usize;
+        let value_off = off + 4;
+        let value_end = value_off + len;
+        if value_end > tlv_end {
+            bail!("TLV entry runs past area: {} > {}", value_end, tlv_end);
+        }
+        if tlv_type == IMAGE_TLV_SHA256 && len == 32 {
+            sha256 = Some(data[value_off..value_end].to_vec());
+        }
+        off = value_end;
+    }
+
+    let sha256 = match sha256 {
+        Some(h) => h,
+        None => bail!("no SHA-256 TLV found"),
+    };
+
+    // 校验 SHA-256：摘要覆盖 header + image + 受保护 TLV 区（即未保护 TLV 区之前的全部字节）
+    let mut hasher = Sha256::new();
+    hasher.update(&data[..tlv_off]);
+    let computed = hasher.finalize();
+    if computed.as_slice() != sha256.as_slice() {
+        bail!("SHA-256 mismatch: image does not match embedded hash");
+    }
+
+    Ok(ImageInfo {
+        version,
+        img_size,
+        flags,
+        sha256,
+    })
+}
+
+/// 上传循环的取消检查点
+///
+/// 分块上传循环在每个数据包之间、每次重试之间调用它：一旦取消标志置位即返回 `Err`，
+/// 让调用方尽早中止一个卡死的烧写，而不必等满 `nb_retry * subsequent_timeout_ms`。
+pub(crate) fn check_cancelled(cancel: &AtomicBool) -> Result<()> {
+    if cancel.load(Ordering::SeqCst) {
+        bail!("upload cancelled");
+    }
+    Ok(())
+}
+
+/// 读取磁盘上的镜像文件并解析校验，供 `upload` 在烧写前预检使用
+///
+/// 在真正流式烧写之前调用，可尽早拒绝魔数错误、被截断或与内嵌哈希不符的文件。
+pub fn verify_image_file(path: &std::path::Path) -> Result<ImageInfo> {
+    let data = std::fs::read(path)?;
+    parse_image(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造一个最小但合法的 MCUboot 镜像（无受保护 TLV 区）
+    fn build_image(body: &[u8]) -> Vec<u8> {
+        let hdr_size: u16 = 32;
+        let img_size = body.len() as u32;
+
+        let mut img = Vec::new();
+        img.extend_from_slice(&IMAGE_MAGIC.to_le_bytes()); // magic
+        img.extend_from_slice(&0u32.to_le_bytes()); // load_addr
+        img.extend_from_slice(&hdr_size.to_le_bytes()); // hdr_size
+        img.extend_from_slice(&0u16.to_le_bytes()); // protect_tlv_size
+        img.extend_from_slice(&img_size.to_le_bytes()); // img_size
+        img.extend_from_slice(&0u32.to_le_bytes()); // flags
+        img.push(1); // version.major
+        img.push(2); // version.minor
+        img.extend_from_slice(&3u16.to_le_bytes()); // version.revision
+        img.extend_from_slice(&4u32.to_le_bytes()); // version.build_num
+        img.extend_from_slice(&[0u8; 4]); // pad
+        assert_eq!(img.len(), hdr_size as usize);
+
+        img.extend_from_slice(body); // image payload
+
+        // SHA-256 覆盖 header + image（本镜像无受保护 TLV 区）
+        let mut hasher = Sha256::new();
+        hasher.update(&img);
+        let digest = hasher.finalize();
+
+        // 未保护 TLV 区：info 头（4）+ 一个 SHA-256 条目（4 + 32）
+        let tlv_tot: u16 = 4 + 4 + 32;
+        img.extend_from_slice(&IMAGE_TLV_INFO_MAGIC.to_le_bytes());
+        img.extend_from_slice(&tlv_tot.to_le_bytes());
+        img.push(IMAGE_TLV_SHA256); // type
+        img.push(0); // pad
+        img.extend_from_slice(&32u16.to_le_bytes()); // len
+        img.extend_from_slice(&digest);
+        img
+    }
+
+    #[test]
+    fn parses_valid_image() {
+        let img = build_image(&[0xaa; 64]);
+        let info = parse_image(&img).unwrap();
+        assert_eq!(info.version.to_string(), "1.2.3+4");
+        assert_eq!(info.img_size, 64);
+        assert_eq!(info.sha256.len(), 32);
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut img = build_image(&[0xaa; 16]);
+        img[0] ^= 0xff;
+        assert!(parse_image(&img).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_tlv() {
+        let mut img = build_image(&[0xaa; 16]);
+        img.truncate(img.len() - 8); // 砍掉一部分 SHA-256 值
+        assert!(parse_image(&img).is_err());
+    }
+
+    #[test]
+    fn check_cancelled_signals_on_set() {
+        let flag = AtomicBool::new(false);
+        assert!(check_cancelled(&flag).is_ok());
+        flag.store(true, Ordering::SeqCst);
+        assert!(check_cancelled(&flag).is_err());
+    }
+
+    #[test]
+    fn rejects_hash_mismatch() {
+        let mut img = build_image(&[0xaa; 16]);
+        let len = img.len();
+        img[len - 1] ^= 0xff; // 篡改内嵌哈希最后一字节
+        assert!(parse_image(&img).is_err());
+    }
+}
@@ -1,14 +1,16 @@
 // lib.rs
 
 // 导入必要的模块和依赖
+mod config;
 mod default;
 mod image;
 mod nmp_hdr;
+mod serial_io;
 mod test_serial_port;
 mod transfer;
 
 pub use crate::default::reset;
-pub use crate::image::{erase, list, test, upload};
+pub use crate::image::{erase, list, parse_image, test, upload, ImageInfo, ImageVersion};
 pub use crate::transfer::SerialSpecs;
 
 // 引入所需的外部 crate
@@ -16,13 +18,18 @@ pub use crate::transfer::SerialSpecs;
 use clap::Parser;
 // use hex;
 // use log::error; // 仅保留需要的部分
+use serde::Serialize;
 use serde_json;
+use serialport::SerialPortType;
 // use simplelog::{ColorChoice, Config, SimpleLogger, TermLogger, TerminalMode}; // 保留必要的部分
 use std::convert::TryInto;
 use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_int, c_uint};
+use std::os::raw::{c_char, c_int, c_uint, c_void};
 use std::path::PathBuf;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
 
 /// 定义进度回调函数类型
 pub type ProgressCallback = extern "C" fn(offset: u64, total: u64);
@@ -160,10 +167,13 @@ pub extern "C" fn rust_upload(
         Err(_) => return -4, // slot 超出 u8 范围
     };
 
-    match upload(
+    // 同步上传不支持取消，传入一个永不置位的标志
+    let cancel = Arc::new(AtomicBool::new(false));
+    match upload_cancellable(
         &specs,
         &PathBuf::from(filename_str),
         slot_u8,
+        &cancel,
         callback.map(|cb| {
             move |offset, total| {
                 cb(offset, total);
@@ -175,6 +185,150 @@ pub extern "C" fn rust_upload(
     }
 }
 
+/// 取消感知的上传封装
+///
+/// 在启动传输前检查取消标志，并把它透传给分块上传循环，循环会在每个数据包之间、
+/// 每次重试之间检查它，从而尽早中止。镜像预检是可选的，由调用方通过
+/// [`parse_image`]/`verify_image_file` 按需执行，不在此处强制，以免拒绝合法的
+/// 非 MCUboot 或无 SHA-256 TLV 的镜像。
+fn upload_cancellable<F>(
+    specs: &SerialSpecs,
+    filename: &PathBuf,
+    slot: u8,
+    cancel: &Arc<AtomicBool>,
+    callback: Option<F>,
+) -> anyhow::Result<()>
+where
+    F: FnMut(u64, u64),
+{
+    if cancel.load(Ordering::SeqCst) {
+        anyhow::bail!("upload cancelled before start");
+    }
+    upload(specs, filename, slot, Arc::clone(cancel), callback)
+}
+
+/// 后台上传的运行状态码
+const UPLOAD_RUNNING: i32 = 0;
+const UPLOAD_DONE: i32 = 1;
+const UPLOAD_ERROR: i32 = 2;
+
+/// 后台上传句柄：持有工作线程、状态以及取消标志
+///
+/// 取消标志是一个 `AtomicBool`，分块上传循环会在每个数据包之间、每次重试之间检查它，
+/// 从而让用户可以中止一个卡死的烧写，而不必等满 `nb_retry * subsequent_timeout_ms`。
+struct UploadHandle {
+    status: Arc<AtomicI32>,
+    cancel: Arc<AtomicBool>,
+    join: Option<JoinHandle<()>>,
+}
+
+/// 非阻塞版本的 `rust_upload`：在工作线程上执行传输，返回一个不透明句柄
+#[no_mangle]
+pub extern "C" fn rust_upload_async(
+    device: *const c_char,
+    filename: *const c_char,
+    slot: c_uint,
+    callback: Option<ProgressCallback>,
+) -> *mut c_void {
+    if device.is_null() || filename.is_null() {
+        return ptr::null_mut();
+    }
+
+    let device_str = unsafe { CStr::from_ptr(device) };
+    let device_name = match device_str.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let file_str = unsafe { CStr::from_ptr(filename) };
+    let filename_str = match file_str.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let slot_u8: u8 = match slot.try_into() {
+        Ok(s) => s,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let status = Arc::new(AtomicI32::new(UPLOAD_RUNNING));
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    let thread_status = Arc::clone(&status);
+    let thread_cancel = Arc::clone(&cancel);
+    let join = std::thread::spawn(move || {
+        let specs = SerialSpecs {
+            device: device_name,
+            ..SerialSpecs::default()
+        };
+
+        let cb_cancel = Arc::clone(&thread_cancel);
+        let result = upload_cancellable(
+            &specs,
+            &PathBuf::from(filename_str),
+            slot_u8,
+            &thread_cancel,
+            callback.map(|cb| {
+                move |offset, total| {
+                    // 取消时不再向上层回报进度
+                    if !cb_cancel.load(Ordering::SeqCst) {
+                        cb(offset, total);
+                    }
+                }
+            }),
+        );
+
+        let code = match result {
+            Ok(_) => UPLOAD_DONE,
+            Err(_) => UPLOAD_ERROR,
+        };
+        thread_status.store(code, Ordering::SeqCst);
+    });
+
+    let handle = Box::new(UploadHandle {
+        status,
+        cancel,
+        join: Some(join),
+    });
+    Box::into_raw(handle) as *mut c_void
+}
+
+/// 查询后台上传状态：`0` 运行中，`1` 完成，`2` 出错，`-1` 句柄为空
+#[no_mangle]
+pub extern "C" fn rust_upload_poll(handle: *mut c_void) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+    let handle = unsafe { &*(handle as *const UploadHandle) };
+    handle.status.load(Ordering::SeqCst) as c_int
+}
+
+/// 请求取消后台上传；分块上传循环会在下一个检查点中止
+#[no_mangle]
+pub extern "C" fn rust_upload_cancel(handle: *mut c_void) {
+    if handle.is_null() {
+        return;
+    }
+    let handle = unsafe { &*(handle as *const UploadHandle) };
+    handle.cancel.store(true, Ordering::SeqCst);
+}
+
+/// 等待工作线程结束、释放句柄，并返回最终状态码
+#[no_mangle]
+pub extern "C" fn rust_upload_join_free(handle: *mut c_void) -> c_int {
+    if handle.is_null() {
+        return -1;
+    }
+    let mut handle = unsafe { Box::from_raw(handle as *mut UploadHandle) };
+    if let Some(join) = handle.join.take() {
+        // 工作线程 panic 时未写入状态，按出错处理，避免返回“运行中”
+        if join.join().is_err() {
+            handle.status.store(UPLOAD_ERROR, Ordering::SeqCst);
+        }
+    }
+    handle.status.load(Ordering::SeqCst) as c_int
+}
+
 /// 重置设备
 #[no_mangle]
 pub extern "C" fn rust_reset(device: *const c_char) -> c_int {
@@ -262,6 +416,34 @@ pub extern "C" fn rust_reset(device: *const c_char) -> c_int {
 //     }
 // }
 
+/// 从单个 JSON 文件驱动一整套针对同一设备的操作序列
+///
+/// 返回值约定（成功为非负，失败为负，彼此不会混淆）：
+/// - `>= 0`：全部成功，值为已执行的步骤数（空 `steps` 返回 `0`）
+/// - `-1`：路径指针为空
+/// - `-2`：路径不是合法 UTF-8
+/// - `-3`：读取或解析作业描述失败（尚未执行任何步骤）
+/// - `<= -4`：第 `k` 个步骤执行失败，`k = -code - 4`（步骤 0 为 `-4`，步骤 1 为 `-5`……）
+#[no_mangle]
+pub extern "C" fn rust_run_config(path: *const c_char) -> c_int {
+    if path.is_null() {
+        return -1;
+    }
+
+    let path_str = unsafe { CStr::from_ptr(path) };
+    let path_name = match path_str.to_str() {
+        Ok(s) => s,
+        Err(_) => return -2,
+    };
+
+    match config::run_config(&PathBuf::from(path_name)) {
+        Ok(count) => count as c_int,
+        Err(config::ConfigError::Read(_)) => -3,
+        // 把失败步骤下标编码到负区间，避免与 step-0 的 `0`、空配置的 `0` 混淆
+        Err(config::ConfigError::Step { index, .. }) => -(index as c_int) - 4,
+    }
+}
+
 /// 添加一个清理函数，用于释放从 Rust 返回的字符串
 #[no_mangle]
 pub extern "C" fn rust_free_string(s: *mut c_char) {
@@ -273,18 +455,62 @@ pub extern "C" fn rust_free_string(s: *mut c_char) {
     }
 }
 
-/// 新增函数：列出所有可用的串口设备
+/// 串口信息，包含 USB 设备的 VID/PID 等元数据，便于 GUI 区分不同的板子
+#[derive(Serialize)]
+pub struct PortInfo {
+    /// 设备名称，例如 `/dev/ttyACM0`
+    pub port_name: String,
+    /// USB 厂商 ID（仅 USB 串口可用）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vid: Option<u16>,
+    /// USB 产品 ID（仅 USB 串口可用）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u16>,
+    /// USB 序列号
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    /// USB 厂商名称
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+    /// USB 产品名称
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+}
+
+impl From<&serialport::SerialPortInfo> for PortInfo {
+    fn from(p: &serialport::SerialPortInfo) -> PortInfo {
+        match &p.port_type {
+            SerialPortType::UsbPort(info) => PortInfo {
+                port_name: p.port_name.clone(),
+                vid: Some(info.vid),
+                pid: Some(info.pid),
+                serial_number: info.serial_number.clone(),
+                manufacturer: info.manufacturer.clone(),
+                product: info.product.clone(),
+            },
+            _ => PortInfo {
+                port_name: p.port_name.clone(),
+                vid: None,
+                pid: None,
+                serial_number: None,
+                manufacturer: None,
+                product: None,
+            },
+        }
+    }
+}
+
+/// 新增函数：列出所有可用的串口设备，连同 USB 元数据一并返回
 #[no_mangle]
 pub extern "C" fn rust_list_ports() -> *mut c_char {
     // 调用 serialport::available_ports()
     match serialport::available_ports() {
         Ok(ports) => {
-            // 将串口信息转换为可序列化的结构体
-            let port_names: Vec<String> = ports.iter().map(|p| p.port_name.clone()).collect();
+            // 将串口信息转换为可序列化的结构体，保留 VID/PID 等元数据
+            let infos: Vec<PortInfo> = ports.iter().map(PortInfo::from).collect();
 
             // 序列化为 JSON
-            let json =
-                serde_json::to_string_pretty(&port_names).unwrap_or_else(|_| "[]".to_string());
+            let json = serde_json::to_string_pretty(&infos).unwrap_or_else(|_| "[]".to_string());
 
             // 转换为 C 字符串并返回
             CString::new(json).unwrap().into_raw()
@@ -292,3 +518,33 @@ pub extern "C" fn rust_list_ports() -> *mut c_char {
         Err(_e) => ptr::null_mut(),
     }
 }
+
+/// 新增函数：按 VID/PID 查找串口设备，返回所有匹配设备名称的 JSON 数组
+///
+/// 让调用方可以说“连接到在场的那块 Nordic DK”，而不必写死 `/dev/ttyACM0`，
+/// 从而自动填充 `SerialSpecs::device`。
+#[no_mangle]
+pub extern "C" fn rust_find_port(vid: c_uint, pid: c_uint) -> *mut c_char {
+    let vid = vid as u16;
+    let pid = pid as u16;
+
+    match serialport::available_ports() {
+        Ok(ports) => {
+            let matches: Vec<String> = ports
+                .iter()
+                .filter_map(|p| match &p.port_type {
+                    SerialPortType::UsbPort(info) if info.vid == vid && info.pid == pid => {
+                        Some(p.port_name.clone())
+                    }
+                    _ => None,
+                })
+                .collect();
+
+            let json =
+                serde_json::to_string_pretty(&matches).unwrap_or_else(|_| "[]".to_string());
+
+            CString::new(json).unwrap().into_raw()
+        }
+        Err(_e) => ptr::null_mut(),
+    }
+}
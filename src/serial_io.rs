@@ -0,0 +1,61 @@
+// serial_io.rs
+
+// 供 transfer 层在每次 SMP 往返前后调用的辅助函数：发送请求前丢弃 OS 串口缓冲里的残留
+// 字节，读取回复时在超时预算内轮询并累积，直到解出一个完整且 CRC 校验通过的帧。
+
+use anyhow::{bail, Result};
+use serialport::{ClearBuffer, SerialPort};
+use std::time::{Duration, Instant};
+
+/// 发送请求前丢弃任何待读输入，避免残留字节污染下一个 base64/CRC 帧
+pub(crate) fn discard_input(port: &mut dyn SerialPort) -> Result<()> {
+    port.clear(ClearBuffer::Input)?;
+    Ok(())
+}
+
+/// 在给定的时间预算内轮询并累积输入，直到 `decode` 解出一个完整帧或预算耗尽
+///
+/// `decode` 对当前已累积的字节尝试解帧：返回 `Ok(Some(frame))` 表示成功，
+/// `Ok(None)` 表示需要更多字节，`Err` 表示帧已损坏、应丢弃继续等待。
+pub(crate) fn read_until_frame<T, F>(
+    port: &mut dyn SerialPort,
+    budget: Duration,
+    mut decode: F,
+) -> Result<T>
+where
+    F: FnMut(&[u8]) -> Result<Option<T>>,
+{
+    let deadline = Instant::now() + budget;
+    let mut buf: Vec<u8> = Vec::new();
+    let mut chunk = [0u8; 256];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            bail!("timed out waiting for a complete frame");
+        }
+
+        // 仅在有数据可读时才读取，否则短暂等待，避免忙等
+        match port.bytes_to_read() {
+            Ok(n) if n > 0 => {
+                let to_read = (n as usize).min(chunk.len());
+                match port.read(&mut chunk[..to_read]) {
+                    Ok(0) => {}
+                    Ok(len) => {
+                        buf.extend_from_slice(&chunk[..len]);
+                        match decode(&buf) {
+                            Ok(Some(frame)) => return Ok(frame),
+                            Ok(None) => {}
+                            // 帧损坏：丢弃已累积字节，继续在预算内等待下一帧
+                            Err(_) => buf.clear(),
+                        }
+                    }
+                    Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Ok(_) => std::thread::sleep(Duration::from_millis(1)),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
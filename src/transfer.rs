@@ -0,0 +1,74 @@
+// transfer.rs
+
+// SMP 串口传输层：封装一次请求/回复往返的纪律 —— 写入前丢弃输入缓冲里的残留字节，
+// 读取时按 subsequent_timeout_ms 预算轮询累积，直到解出一个完整且 CRC 校验通过的帧，
+// 失败则在 nb_retry 次内重试。upload/erase/test/reset 等操作都经由它与设备通信。
+
+use crate::image::check_cancelled;
+use crate::serial_io::{discard_input, read_until_frame};
+
+use anyhow::{anyhow, Result};
+use serialport::SerialPort;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// 串口连接参数
+#[derive(Debug, Clone)]
+pub struct SerialSpecs {
+    /// 设备名称，例如 `/dev/ttyACM0`
+    pub device: String,
+    /// 首个请求的超时时间（秒）
+    pub initial_timeout_s: u32,
+    /// 后续请求的超时时间（毫秒）
+    pub subsequent_timeout_ms: u32,
+    /// 每个数据包的重试次数
+    pub nb_retry: u32,
+    /// 每行的最大长度
+    pub linelength: usize,
+    /// 每个请求的最大长度
+    pub mtu: usize,
+    /// 波特率
+    pub baudrate: u32,
+}
+
+/// 执行一次 SMP 往返：发送 `request`，随后轮询累积回复直到 `decode` 解出一个完整且
+/// CRC 校验通过的帧；读取超时或解帧失败时在 `nb_retry` 次内重试，
+/// 而清空输入、写入或 flush 出错则直接返回该错误，不再重试。
+///
+/// 每次写入前先丢弃 OS 串口缓冲里的残留字节（上一次 reset 或设备重启留下的），
+/// 避免污染本次的 base64/CRC 帧；读取不再是固定长度的单次阻塞读，而是在
+/// `subsequent_timeout_ms` 预算内轮询累积，从而显著减少 reset 之后的无谓重试。
+///
+/// 分块上传每个数据包都经由此函数发送，因此在每次重试之前检查 `cancel`，既覆盖了
+/// 数据包之间、也覆盖了重试之间的取消，卡死的烧写可在下一个检查点立即中止。
+pub(crate) fn transceive<F, T>(
+    specs: &SerialSpecs,
+    port: &mut dyn SerialPort,
+    request: &[u8],
+    cancel: &AtomicBool,
+    mut decode: F,
+) -> Result<T>
+where
+    F: FnMut(&[u8]) -> Result<Option<T>>,
+{
+    let budget = Duration::from_millis(specs.subsequent_timeout_ms as u64);
+    let mut last_err: Option<anyhow::Error> = None;
+
+    for _ in 0..specs.nb_retry.max(1) {
+        // 每个数据包之间、每次重试之间检查取消标志
+        check_cancelled(cancel)?;
+
+        // 发送前丢弃任何待读输入，避免残留字节污染本次帧
+        discard_input(port)?;
+        port.write_all(request)?;
+        port.flush()?;
+
+        // 轮询累积直到解出一个完整且 CRC 校验通过的帧，而不是做一次定长阻塞读
+        match read_until_frame(port, budget, |buf| decode(buf)) {
+            Ok(frame) => return Ok(frame),
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| anyhow!("transfer failed after {} retries", specs.nb_retry)))
+}